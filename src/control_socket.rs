@@ -0,0 +1,135 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{UnixListener, UnixStream};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::shutdown::drain;
+use crate::utils::AppState;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ControlOp {
+    ListRunningJobs,
+    GetNodeStatus,
+    PauseIntake,
+    ResumeIntake,
+    TriggerDrain,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ControlResponse {
+    RunningJobs(Vec<String>),
+    NodeStatus {
+        registered: bool,
+        last_block_seen: u64,
+        free_cgroups: usize,
+    },
+    Ack,
+    Err(String),
+}
+
+// Listen on a unix domain socket for a small length-prefixed, JSON-framed opcode protocol so an
+// operator inside the enclave can inspect or steer a running executor without the on-chain
+// registration endpoints in `node_handler`.
+pub async fn serve(socket_path: String, app_state: Data<AppState>, drain_timeout: Duration) {
+    let _ = std::fs::remove_file(&socket_path);
+
+    let listener = match UnixListener::bind(&socket_path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!(
+                "Failed to bind the control socket at {}: {:?}",
+                socket_path, err
+            );
+            return;
+        }
+    };
+
+    println!("Control socket listening at {}", socket_path);
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                eprintln!("Failed to accept a control socket connection: {:?}", err);
+                continue;
+            }
+        };
+
+        let app_state = app_state.clone();
+        tokio::spawn(async move {
+            handle_connection(stream, app_state, drain_timeout).await;
+        });
+    }
+}
+
+async fn handle_connection(stream: UnixStream, app_state: Data<AppState>, drain_timeout: Duration) {
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    while let Some(frame) = framed.next().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(err) => {
+                eprintln!("Control socket connection error: {:?}", err);
+                return;
+            }
+        };
+
+        let response = match serde_json::from_slice::<ControlOp>(&frame) {
+            Ok(op) => dispatch(op, &app_state, drain_timeout).await,
+            Err(err) => ControlResponse::Err(format!("Failed to decode opcode: {:?}", err)),
+        };
+
+        let Ok(encoded) = serde_json::to_vec(&response) else {
+            eprintln!("Failed to encode control socket response");
+            return;
+        };
+
+        if framed.send(encoded.into()).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn dispatch(
+    op: ControlOp,
+    app_state: &Data<AppState>,
+    drain_timeout: Duration,
+) -> ControlResponse {
+    match op {
+        ControlOp::ListRunningJobs => ControlResponse::RunningJobs(
+            app_state
+                .job_requests_running
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|job_id| job_id.to_string())
+                .collect(),
+        ),
+        ControlOp::GetNodeStatus => ControlResponse::NodeStatus {
+            registered: app_state.enclave_registered.load(Ordering::Relaxed),
+            last_block_seen: app_state.last_block_seen.load(Ordering::Relaxed),
+            free_cgroups: app_state.cgroups.lock().unwrap().free.len(),
+        },
+        ControlOp::PauseIntake => {
+            app_state.intake_paused.store(true, Ordering::Relaxed);
+            println!("Job intake paused over the control socket");
+            ControlResponse::Ack
+        }
+        ControlOp::ResumeIntake => {
+            app_state.intake_paused.store(false, Ordering::Relaxed);
+            println!("Job intake resumed over the control socket");
+            ControlResponse::Ack
+        }
+        ControlOp::TriggerDrain => {
+            let app_state = app_state.clone();
+            tokio::spawn(async move {
+                drain(app_state, drain_timeout).await;
+            });
+            ControlResponse::Ack
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use ethers::types::U256;
+use rusqlite::{params, Connection};
+
+// Embedded, file-backed state store that lets in-flight jobs, the last processed block and
+// registration state survive a restart, instead of resetting to 'starting_block' and silently
+// abandoning jobs whose timeout tasks were lost with the old process.
+pub struct DbCtx {
+    conn: Mutex<Connection>,
+}
+
+pub struct RunningJob {
+    pub job_id: U256,
+    pub code_hash: String,
+    pub code_inputs: Vec<u8>,
+    pub deadline: u64,
+    pub is_selected: bool,
+}
+
+impl DbCtx {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path).context("Failed to open the sqlite state store")?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS node_state (
+                id INTEGER PRIMARY KEY CHECK (id = 0),
+                last_block_seen INTEGER NOT NULL,
+                enclave_registered INTEGER NOT NULL
+            );
+            INSERT OR IGNORE INTO node_state (id, last_block_seen, enclave_registered)
+                VALUES (0, 0, 0);
+            CREATE TABLE IF NOT EXISTS running_jobs (
+                job_id TEXT PRIMARY KEY,
+                code_hash TEXT NOT NULL,
+                code_inputs BLOB NOT NULL,
+                deadline INTEGER NOT NULL,
+                is_selected INTEGER NOT NULL
+            );",
+        )
+        .context("Failed to initialize the state store schema")?;
+
+        Ok(Self { conn: conn.into() })
+    }
+
+    pub fn last_block_seen(&self) -> Result<u64> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT last_block_seen FROM node_state WHERE id = 0",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|block| block as u64)
+            .context("Failed to read last_block_seen from the state store")
+    }
+
+    pub fn set_last_block_seen(&self, block: u64) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE node_state SET last_block_seen = ?1 WHERE id = 0",
+                params![block as i64],
+            )
+            .context("Failed to persist last_block_seen")?;
+        Ok(())
+    }
+
+    pub fn registration_state(&self) -> Result<bool> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT enclave_registered FROM node_state WHERE id = 0",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|flag| flag != 0)
+            .context("Failed to read enclave_registered from the state store")
+    }
+
+    pub fn set_registration_state(&self, registered: bool) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE node_state SET enclave_registered = ?1 WHERE id = 0",
+                params![registered as i64],
+            )
+            .context("Failed to persist enclave_registered")?;
+        Ok(())
+    }
+
+    pub fn insert_running_job(
+        &self,
+        job_id: U256,
+        code_hash: &str,
+        code_inputs: &[u8],
+        deadline: u64,
+        is_selected: bool,
+    ) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO running_jobs
+                    (job_id, code_hash, code_inputs, deadline, is_selected)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    job_id.to_string(),
+                    code_hash,
+                    code_inputs,
+                    deadline as i64,
+                    is_selected as i64
+                ],
+            )
+            .context("Failed to persist running job")?;
+        Ok(())
+    }
+
+    pub fn remove_running_job(&self, job_id: U256) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM running_jobs WHERE job_id = ?1",
+                params![job_id.to_string()],
+            )
+            .context("Failed to remove completed job from the state store")?;
+        Ok(())
+    }
+
+    pub fn running_jobs(&self) -> Result<Vec<RunningJob>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT job_id, code_hash, code_inputs, deadline, is_selected FROM running_jobs",
+        )?;
+
+        stmt.query_map([], |row| {
+            let job_id: String = row.get(0)?;
+            Ok(RunningJob {
+                job_id: U256::from_dec_str(&job_id).unwrap_or_default(),
+                code_hash: row.get(1)?,
+                code_inputs: row.get(2)?,
+                deadline: row.get::<_, i64>(3)? as u64,
+                is_selected: row.get::<_, i64>(4)? != 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to read running jobs from the state store")
+    }
+}
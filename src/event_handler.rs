@@ -1,8 +1,9 @@
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use actix_web::web::Data;
 use ethers::abi::{decode, ParamType};
-use ethers::providers::{Middleware, Provider, StreamExt, Ws};
+use ethers::providers::StreamExt;
 use ethers::types::{BigEndianHash, Filter, Log, H256, U64};
 use ethers::utils::keccak256;
 use scopeguard::defer;
@@ -11,27 +12,32 @@ use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio_stream::Stream;
 
 use crate::job_handler::handle_job;
+use crate::log_backfill::backfill_logs;
+use crate::log_poller::EventSource;
 use crate::timeout_handler::handle_timeout;
-use crate::utils::{send_txn, AppState, JobResponse, Jobs};
+use crate::txn_manager::send_job_txn;
+use crate::utils::{AppState, JobResponse, Jobs};
 
 // Start listening to Job requests emitted by the Jobs contract if enclave is registered else listen for Executor registered events first
 pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
     defer! {
         *app_state.events_listener_active.lock().unwrap() = false;
     }
+    let poll_interval = Duration::from_millis(app_state.poll_interval_ms);
+
     loop {
-        // web socket connection
-        let web_socket_client =
-            match Provider::<Ws>::connect_with_reconnects(&app_state.ws_rpc_url, 0).await {
-                Ok(client) => client,
-                Err(err) => {
-                    eprintln!(
-                        "Failed to connect to the common chain websocket provider: {:?}",
-                        err
-                    );
-                    continue;
-                }
-            };
+        // Connect over the websocket when configured, falling back to polling
+        // 'eth_getFilterChanges' over the http rpc client otherwise
+        let event_source = match EventSource::connect(&app_state).await {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!(
+                    "Failed to connect to the common chain rpc provider: {:?}",
+                    err
+                );
+                continue;
+            }
+        };
 
         if !app_state.enclave_registered.load(Ordering::Relaxed) {
             // Create filter to listen to the 'ExecutorRegistered' event emitted by the Executors contract
@@ -44,9 +50,10 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
                 .topic2(H256::from(*app_state.enclave_owner.lock().unwrap()))
                 .from_block(starting_block);
 
-            // Subscribe to the executors filter through the rpc web socket client
-            let mut register_stream = match web_socket_client
-                .subscribe_logs(&register_executor_filter)
+            // Subscribe to the executors filter through the rpc web socket client, or start
+            // polling for it through the rpc http client
+            let mut register_stream = match event_source
+                .subscribe(&register_executor_filter, poll_interval, app_state.clone())
                 .await
             {
                 Ok(stream) => stream,
@@ -66,10 +73,16 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
                 }
 
                 app_state.enclave_registered.store(true, Ordering::Relaxed);
-                app_state.last_block_seen.store(
-                    event.block_number.unwrap_or(starting_block).as_u64(),
-                    Ordering::Relaxed,
-                );
+                if let Err(err) = app_state.db.set_registration_state(true) {
+                    eprintln!("Failed to persist registration state: {:?}", err);
+                }
+                let current_block = event.block_number.unwrap_or(starting_block).as_u64();
+                app_state
+                    .last_block_seen
+                    .store(current_block, Ordering::Relaxed);
+                if let Err(err) = app_state.db.set_last_block_seen(current_block) {
+                    eprintln!("Failed to persist last_block_seen: {:?}", err);
+                }
                 break;
             }
 
@@ -79,17 +92,57 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
         }
 
         println!("Enclave registered successfully on the common chain!");
+
+        // Fetch the current chain head once so both filters backfill against the same ceiling
+        let current_block = match event_source.get_block_number().await {
+            Ok(block) => block.as_u64(),
+            Err(err) => {
+                eprintln!(
+                    "Failed to fetch the current block number, skipping backfill this round: {:?}",
+                    err
+                );
+                app_state.last_block_seen.load(Ordering::Relaxed)
+            }
+        };
+
         // Create filter to listen to relevant events emitted by the Jobs contract
         let jobs_event_filter = Filter::new()
             .address(app_state.jobs_contract_addr)
             .topic0(vec![
                 keccak256("JobCreated(uint256,address,bytes32,bytes,uint256,address[])"),
                 keccak256("JobResponded(uint256,bytes,uint256,uint8,uint8)"),
-            ])
-            .from_block(app_state.last_block_seen.load(Ordering::Relaxed));
-        // Subscribe to the jobs filter through the rpc web socket client
-        let jobs_stream = match web_socket_client.subscribe_logs(&jobs_event_filter).await {
-            Ok(stream) => stream,
+            ]);
+
+        // Catch up on logs emitted between 'last_block_seen' and 'current_block' so events
+        // mined while disconnected aren't lost. Starts one block past 'last_block_seen' since
+        // that block's events were already handled in a prior pass through this loop (or a prior
+        // process run). 'last_block_seen' itself is advanced by 'handle_event_logs' as it
+        // processes the backfilled logs; moving it ahead here would make it skip them as stale
+        let backfill_from = app_state.last_block_seen.load(Ordering::Relaxed) + 1;
+        let mut jobs_backfilled_to = backfill_from - 1;
+        let jobs_backfill = backfill_logs(
+            &event_source,
+            &jobs_event_filter,
+            backfill_from,
+            current_block,
+            |window_end| jobs_backfilled_to = window_end,
+        )
+        .await;
+
+        // 'jobs_backfilled_to' is the last block *covered* by the backfill above, so the live
+        // subscription/poller must start one block after it or its first delivered block would
+        // be re-processed a second time
+        let jobs_event_filter = jobs_event_filter.from_block(jobs_backfilled_to + 1);
+        // Subscribe to the jobs filter through the rpc web socket client, or start polling for it
+        // through the rpc http client
+        let jobs_stream = match event_source
+            .subscribe(&jobs_event_filter, poll_interval, app_state.clone())
+            .await
+        {
+            Ok(stream) => Box::pin(tokio_stream::StreamExt::chain(
+                tokio_stream::iter(jobs_backfill),
+                stream,
+            )),
             Err(err) => {
                 eprintln!(
                     "Failed to subscribe to Jobs ({:?}) contract 'JobCreated' and 'JobResponded' event logs: {:?}",
@@ -99,20 +152,38 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
                 continue;
             }
         };
-        let jobs_stream = std::pin::pin!(jobs_stream);
 
         // Create filter to listen to 'ExecutorDeregistered' event emitted by the Executors contract
         let executors_event_filter = Filter::new()
             .address(app_state.executors_contract_addr)
             .topic0(H256::from(keccak256("ExecutorDeregistered(address)")))
-            .topic1(H256::from(app_state.enclave_address))
-            .from_block(app_state.last_block_seen.load(Ordering::Relaxed));
-        // Subscribe to the executors filter through the rpc web socket client
-        let executors_stream = match web_socket_client
-            .subscribe_logs(&executors_event_filter)
+            .topic1(H256::from(app_state.enclave_address));
+
+        // Same starting block as the jobs backfill above: 'last_block_seen' was already handled
+        let mut executors_backfilled_to = backfill_from - 1;
+        let executors_backfill = backfill_logs(
+            &event_source,
+            &executors_event_filter,
+            backfill_from,
+            current_block,
+            |window_end| executors_backfilled_to = window_end,
+        )
+        .await;
+
+        // Same off-by-one guard as the jobs filter above: don't re-deliver the last backfilled
+        // block through the live subscription/poller
+        let executors_event_filter =
+            executors_event_filter.from_block(executors_backfilled_to + 1);
+        // Subscribe to the executors filter through the rpc web socket client, or start polling
+        // for it through the rpc http client
+        let executors_stream = match event_source
+            .subscribe(&executors_event_filter, poll_interval, app_state.clone())
             .await
         {
-            Ok(stream) => stream,
+            Ok(stream) => Box::pin(tokio_stream::StreamExt::chain(
+                tokio_stream::iter(executors_backfill),
+                stream,
+            )),
             Err(err) => {
                 eprintln!(
                     "Failed to subscribe to Executors ({:?}) contract 'ExecutorDeregistered' event logs: {:?}",
@@ -122,7 +193,6 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
                 continue;
             }
         };
-        let executors_stream = std::pin::pin!(executors_stream);
 
         // Create tokio mpsc channel to receive contract events and send transactions to them
         let (tx, rx) = channel::<JobResponse>(100);
@@ -140,7 +210,7 @@ pub async fn events_listener(app_state: Data<AppState>, starting_block: U64) {
 }
 
 // Receive job execution responses and send the resulting transactions to the common chain
-async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobResponse>) {
+pub async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobResponse>) {
     while let Some(job_response) = rx.recv().await {
         let Some(job_output) = job_response.job_output else {
             let Some(job_id) = job_response.timeout_response else {
@@ -154,7 +224,12 @@ async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobRe
             )
             .slash_on_execution_timeout(job_id);
 
-            let txn_result = send_txn(txn).await;
+            // Retries, gas bumps, and nonce recovery for this submission are handled inside
+            // 'send_job_txn'. Responses on this channel are submitted one at a time, so this
+            // never overlaps an output submission read from the same channel; 'send_job_txn'
+            // only coalesces it with a submission for the same job that another
+            // 'send_execution_output' task has in flight at that moment
+            let txn_result = send_job_txn(job_id, txn).await;
             let Ok(_) = txn_result else {
                 eprintln!(
                     "Failed to submit the execution timeout transaction: {:?}",
@@ -163,6 +238,13 @@ async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobRe
                 continue;
             };
 
+            if let Err(err) = app_state.db.remove_running_job(job_id) {
+                eprintln!(
+                    "Failed to remove timed out job {} from the state store: {:?}",
+                    job_id, err
+                );
+            }
+
             continue;
         };
 
@@ -180,7 +262,7 @@ async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobRe
             job_output.sign_timestamp,
         );
 
-        let txn_result = send_txn(txn).await;
+        let txn_result = send_job_txn(job_output.id, txn).await;
         let Ok(_) = txn_result else {
             eprintln!(
                 "Failed to submit the execution output transaction: {:?}",
@@ -188,6 +270,17 @@ async fn send_execution_output(app_state: Data<AppState>, mut rx: Receiver<JobRe
             );
             continue;
         };
+
+        // The output transaction is this node's half of resolving the job; the other half
+        // ('JobResponded' reaching output_count) is handled in 'handle_event_logs', but marking
+        // it resolved here too means a restart right after a successful submission doesn't
+        // re-run execution for a job this node already answered
+        if let Err(err) = app_state.db.remove_running_job(job_output.id) {
+            eprintln!(
+                "Failed to remove completed job {} from the state store: {:?}",
+                job_output.id, err
+            );
+        }
     }
 
     println!("Transaction sender channel stopped!");
@@ -213,6 +306,9 @@ pub async fn handle_event_logs(
                 // Capture the Executor deregistered event emitted by the executors contract
                 println!("Enclave deregistered from the common chain!");
                 app_state.enclave_registered.store(false, Ordering::Relaxed);
+                if let Err(err) = app_state.db.set_registration_state(false) {
+                    eprintln!("Failed to persist registration state: {:?}", err);
+                }
 
                 println!("Stopped listening to job events!");
                 return;
@@ -230,12 +326,24 @@ pub async fn handle_event_logs(
                     continue;
                 }
                 app_state.last_block_seen.store(current_block.as_u64(), Ordering::Relaxed);
+                if let Err(err) = app_state.db.set_last_block_seen(current_block.as_u64()) {
+                    eprintln!("Failed to persist last_block_seen: {:?}", err);
+                }
 
                 // Capture the Job created event emitted by the jobs contract
                 if event.topics[0]
                     == keccak256("JobCreated(uint256,address,bytes32,bytes,uint256,address[])")
                     .into()
                 {
+                    // Draining for shutdown, or paused from the control socket: stop taking on
+                    // new jobs, but keep processing 'JobResponded' below so already-running jobs
+                    // can still be marked resolved
+                    if app_state.draining.load(Ordering::Relaxed)
+                        || app_state.intake_paused.load(Ordering::Relaxed)
+                    {
+                        continue;
+                    }
+
                     // Decode the event parameters using the ABI information
                     let event_tokens = decode(
                         &vec![
@@ -301,6 +409,21 @@ pub async fn handle_event_logs(
                         .filter(|addr| addr.is_some())
                         .any(|addr| addr.unwrap() == app_state.enclave_address);
 
+                    let code_hash =
+                        String::from("0x".to_owned() + &data_encoding::HEXLOWER.encode(&code_hash));
+
+                    // Write through to the state store so the timeout (and, if selected, the
+                    // execution) task can be resumed if the process restarts before it resolves
+                    if let Err(err) = app_state.db.insert_running_job(
+                        job_id,
+                        &code_hash,
+                        &code_inputs,
+                        user_deadline.as_u64(),
+                        is_node_selected,
+                    ) {
+                        eprintln!("Failed to persist running job {}: {:?}", job_id, err);
+                    }
+
                     let app_state_clone = app_state.clone();
                     let tx_clone = tx.clone();
 
@@ -309,8 +432,6 @@ pub async fn handle_event_logs(
                     });
 
                     if is_node_selected {
-                        let code_hash =
-                            String::from("0x".to_owned() + &data_encoding::HEXLOWER.encode(&code_hash));
                         let app_state_clone = app_state.clone();
                         let tx_clone = tx.clone();
 
@@ -367,6 +488,9 @@ pub async fn handle_event_logs(
                             .lock()
                             .unwrap()
                             .remove(&job_id);
+                        if let Err(err) = app_state.db.remove_running_job(job_id) {
+                            eprintln!("Failed to remove resolved job {} from the state store: {:?}", job_id, err);
+                        }
                     }
                 }
             }
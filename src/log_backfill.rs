@@ -0,0 +1,44 @@
+use ethers::types::{Filter, Log};
+
+use crate::log_poller::EventSource;
+
+// Max block range requested per 'eth_getLogs' call, chosen to stay comfortably under the
+// result-size limits most rpc providers impose on a single request.
+const BACKFILL_CHUNK_BLOCKS: u64 = 5_000;
+
+// Fetch every log matching `filter` across `[from_block, to_block]` in bounded windows, so
+// `subscribe_logs`/the http poller - which only deliver logs mined after they attach - don't
+// silently drop events emitted while the executor was down or reconnecting. `on_window_done` is
+// called with the last block covered after each window so the caller can persist progress and
+// resume a failed backfill instead of refetching everything already seen.
+pub async fn backfill_logs(
+    event_source: &EventSource,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    mut on_window_done: impl FnMut(u64),
+) -> Vec<Log> {
+    let mut logs = Vec::new();
+    let mut window_start = from_block;
+
+    while window_start <= to_block {
+        let window_end = (window_start + BACKFILL_CHUNK_BLOCKS - 1).min(to_block);
+        let window_filter = filter.clone().from_block(window_start).to_block(window_end);
+
+        match event_source.get_logs(&window_filter).await {
+            Ok(window_logs) => logs.extend(window_logs),
+            Err(err) => {
+                eprintln!(
+                    "Failed to backfill logs for blocks {}-{}, resuming from {} on the next attempt: {:?}",
+                    window_start, window_end, window_start, err
+                );
+                break;
+            }
+        }
+
+        on_window_done(window_end);
+        window_start = window_end + 1;
+    }
+
+    logs
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use ethers::providers::{FilterKind, Http, Middleware, Provider, ProviderError, Ws};
+use ethers::types::{Filter, Log, U256, U64};
+use tokio::sync::mpsc::channel;
+use tokio::time::sleep;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+
+use crate::utils::AppState;
+
+// Either side of the "WS subscription vs HTTP polling" ingestion path, picked once per
+// `events_listener` connection attempt based on whether `web_socket_url` is configured.
+pub enum EventSource {
+    Ws(Provider<Ws>),
+    Http(Arc<Provider<Http>>),
+}
+
+impl EventSource {
+    // Connect using whichever transport the operator configured, preferring the websocket when
+    // both are available since it delivers logs without a poll-interval delay.
+    pub async fn connect(app_state: &AppState) -> Result<Self, ProviderError> {
+        if !app_state.ws_rpc_url.is_empty() {
+            Provider::<Ws>::connect_with_reconnects(&app_state.ws_rpc_url, 0)
+                .await
+                .map(EventSource::Ws)
+        } else {
+            Provider::<Http>::try_from(app_state.http_rpc_url.as_str())
+                .map(|client| EventSource::Http(Arc::new(client)))
+                .map_err(|err| ProviderError::CustomError(err.to_string()))
+        }
+    }
+
+    // Produce a `Log` stream for `filter`, backed by a live WS subscription or, in the HTTP case,
+    // by a background poller started on `eth_newFilter`/`eth_getFilterChanges`.
+    pub async fn subscribe<'a>(
+        &'a self,
+        filter: &Filter,
+        poll_interval: Duration,
+        app_state: Data<AppState>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Log> + Send + 'a>>, ProviderError> {
+        match self {
+            EventSource::Ws(client) => {
+                let stream = client.subscribe_logs(filter).await?;
+                Ok(Box::pin(stream))
+            }
+            EventSource::Http(client) => Ok(Box::pin(poll_logs(
+                client.clone(),
+                filter.clone(),
+                poll_interval,
+                app_state,
+            ))),
+        }
+    }
+
+    // Fetch logs matching `filter` via 'eth_getLogs', used to backfill the gap a live
+    // subscription/poller cannot see on its own.
+    pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, ProviderError> {
+        match self {
+            EventSource::Ws(client) => client.get_logs(filter).await,
+            EventSource::Http(client) => client.get_logs(filter).await,
+        }
+    }
+
+    pub async fn get_block_number(&self) -> Result<U64, ProviderError> {
+        match self {
+            EventSource::Ws(client) => client.get_block_number().await,
+            EventSource::Http(client) => client.get_block_number().await,
+        }
+    }
+}
+
+// Poll `eth_getFilterChanges` on `filter` at `poll_interval`, forwarding newly seen logs on the
+// returned stream. If the provider reports the filter has expired, re-create it from
+// `app_state.last_block_seen` so the gap is covered on the next poll instead of silently
+// dropping logs.
+pub fn poll_logs(
+    http_client: Arc<Provider<Http>>,
+    filter: Filter,
+    poll_interval: Duration,
+    app_state: Data<AppState>,
+) -> impl Stream<Item = Log> + Unpin {
+    let (tx, rx) = channel::<Log>(100);
+
+    tokio::spawn(async move {
+        let mut seen_logs = HashSet::<(U64, U256)>::new();
+        let mut filter_id = None;
+        let mut filter_expired = false;
+
+        loop {
+            if filter_id.is_none() {
+                // The first filter starts at the caller's 'from_block'; only a re-created one
+                // falls back to 'last_block_seen'
+                let filter = if filter_expired {
+                    let from_block = app_state.last_block_seen.load(Ordering::Relaxed);
+                    filter.clone().from_block(from_block)
+                } else {
+                    filter.clone()
+                };
+
+                filter_id = match http_client.new_filter(FilterKind::Logs(&filter)).await {
+                    Ok(id) => Some(id),
+                    Err(err) => {
+                        eprintln!(
+                            "Failed to create an http log filter for address {:?}: {:?}",
+                            filter.address, err
+                        );
+                        sleep(poll_interval).await;
+                        continue;
+                    }
+                };
+            }
+
+            match http_client
+                .get_filter_changes::<_, Log>(filter_id.unwrap())
+                .await
+            {
+                Ok(logs) => {
+                    for log in logs {
+                        let (Some(block_number), Some(log_index)) =
+                            (log.block_number, log.log_index)
+                        else {
+                            continue;
+                        };
+
+                        if !seen_logs.insert((block_number, log_index)) {
+                            continue;
+                        }
+
+                        if tx.send(log).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => {
+                    if err.to_string().contains("filter not found") {
+                        eprintln!("Http log filter expired, re-creating from last seen block");
+                        filter_id = None;
+                        filter_expired = true;
+                        continue;
+                    }
+                    eprintln!("Failed to poll http log filter: {:?}", err);
+                }
+            }
+
+            sleep(poll_interval).await;
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
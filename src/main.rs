@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use actix_web::web::Data;
 use actix_web::{App, HttpServer};
@@ -8,12 +10,21 @@ use ethers::types::{Address, H160};
 use ethers::utils::public_key_to_address;
 use k256::ecdsa::SigningKey;
 use tokio::fs;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::channel;
+use tokio::time::interval;
 
 use serverless::cgroups::Cgroups;
+use serverless::control_socket;
+use serverless::db::DbCtx;
+use serverless::event_handler::send_execution_output;
+use serverless::job_handler::handle_job;
 use serverless::node_handler::{
     export_signed_registration_message, get_executor_details, index, inject_immutable_config,
     inject_mutable_config,
 };
+use serverless::shutdown::drain;
+use serverless::timeout_handler::handle_timeout;
 use serverless::utils::AppState;
 
 // EXECUTOR CONFIGURATION PARAMETERS
@@ -53,6 +64,23 @@ struct Args {
 
     #[clap(long, value_parser, default_value = "3")]
     num_selected_executors: u8,
+
+    // Used only when 'web_socket_url' is not configured, to poll 'eth_getFilterChanges' instead
+    // of subscribing to logs over a websocket
+    #[clap(long, value_parser, default_value = "1000")]
+    poll_interval_ms: u64,
+
+    #[clap(long, value_parser, default_value = "./executor.db")]
+    db_path: String,
+
+    // Bound on how long shutdown waits for in-flight jobs to finish and for the
+    // 'ExecutorDeregistered' confirmation before forcing an exit
+    #[clap(long, value_parser, default_value = "60")]
+    drain_timeout: u64, // time in seconds
+
+    // Path to a unix socket exposing the operator control plane, disabled when empty
+    #[clap(long, value_parser, default_value = "")]
+    control_socket: String,
 }
 
 #[tokio::main]
@@ -77,6 +105,16 @@ async fn main() -> Result<()> {
 
     let enclave_address = public_key_to_address(&enclave_signer_key.verifying_key());
 
+    // Open the persistent state store and restore whatever survived the last run, so a restart
+    // resumes crash-recoverably instead of resetting to 'starting_block' and abandoning jobs
+    let db = Arc::new(DbCtx::open(&cli.db_path).context("Failed to open the state store")?);
+    let last_block_seen = db
+        .last_block_seen()
+        .context("Failed to restore last_block_seen from the state store")?;
+    let enclave_registered = db
+        .registration_state()
+        .context("Failed to restore registration state from the state store")?;
+
     // Initialize App data that will be shared across multiple threads and tasks
     let app_data = Data::new(AppState {
         job_capacity: cgroups.free.len(),
@@ -100,14 +138,91 @@ async fn main() -> Result<()> {
         enclave_signer: enclave_signer_key,
         immutable_params_injected: false.into(),
         mutable_params_injected: false.into(),
-        enclave_registered: false.into(),
+        enclave_registered: enclave_registered.into(),
         events_listener_active: false.into(),
         enclave_owner: H160::zero().into(),
         http_rpc_client: None.into(),
         job_requests_running: HashSet::new().into(),
-        last_block_seen: 0.into(),
+        last_block_seen: last_block_seen.into(),
+        poll_interval_ms: cli.poll_interval_ms,
+        db: db.clone(),
+        draining: false.into(),
+        intake_paused: false.into(),
     });
 
+    if !cli.control_socket.is_empty() {
+        let control_app_data = app_data.clone();
+        let drain_timeout = Duration::from_secs(cli.drain_timeout);
+        tokio::spawn(control_socket::serve(
+            cli.control_socket,
+            control_app_data,
+            drain_timeout,
+        ));
+    }
+
+    // Re-spawn the timeout (and, if this node was selected, the execution) task for every job
+    // that was still running when the process last stopped and whose deadline hasn't passed
+    let (resumed_tx, resumed_rx) = channel(100);
+    tokio::spawn(send_execution_output(app_data.clone(), resumed_rx));
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is before the unix epoch")?
+        .as_secs();
+
+    for job in db
+        .running_jobs()
+        .context("Failed to read running jobs from the state store")?
+    {
+        if job.deadline <= now {
+            // This job can no longer be resolved; drop it instead of reloading and re-skipping
+            // the same row on every future boot
+            if let Err(err) = db.remove_running_job(job.job_id) {
+                eprintln!(
+                    "Failed to remove expired job {} from the state store: {:?}",
+                    job.job_id, err
+                );
+            }
+            continue;
+        }
+
+        // Mark resumed jobs as running before spawning their tasks, so 'shutdown::drain' and the
+        // control socket's status opcodes see them as in-flight instead of treating a restart
+        // with resumed jobs as idle
+        app_data
+            .job_requests_running
+            .lock()
+            .unwrap()
+            .insert(job.job_id);
+
+        let app_state_clone = app_data.clone();
+        let tx_clone = resumed_tx.clone();
+        tokio::spawn(async move {
+            // The mutable config endpoint is what sets 'http_rpc_client'; wait for it so a
+            // resumed job's response doesn't hit the 'unwrap()' in 'send_execution_output' on an
+            // rpc client that hasn't been injected yet on this boot
+            wait_for_http_rpc_client(&app_state_clone).await;
+            handle_timeout(job.job_id, job.deadline, app_state_clone, tx_clone).await;
+        });
+
+        if job.is_selected {
+            let app_state_clone = app_data.clone();
+            let tx_clone = resumed_tx.clone();
+            tokio::spawn(async move {
+                wait_for_http_rpc_client(&app_state_clone).await;
+                handle_job(
+                    job.job_id,
+                    job.code_hash,
+                    job.code_inputs.into(),
+                    job.deadline,
+                    app_state_clone,
+                    tx_clone,
+                )
+                .await;
+            });
+        }
+    }
+
     // Start actix server to expose the executor outside the enclave
     let server = HttpServer::new(move || {
         App::new()
@@ -122,9 +237,37 @@ async fn main() -> Result<()> {
     .context(format!("could not bind to port {}", cli.port))?
     .run();
 
+    let server_handle = server.handle();
+    let drain_timeout = Duration::from_secs(cli.drain_timeout);
+    let shutdown_app_data = app_data.clone();
+
+    // On SIGTERM/SIGINT, drain running jobs and deregister from the common chain before letting
+    // the http server stop accepting connections
+    tokio::spawn(async move {
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Failed to install the SIGTERM handler");
+
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+
+        drain(shutdown_app_data, drain_timeout).await;
+        server_handle.stop(true).await;
+    });
+
     println!("Node server started on port {}", cli.port);
 
     server.await?;
 
     Ok(())
 }
+
+// Poll until the operator has injected the mutable config (the only place that sets
+// 'http_rpc_client'), so tasks resumed at boot don't run ahead of it being available.
+async fn wait_for_http_rpc_client(app_state: &AppState) {
+    let mut ticker = interval(Duration::from_millis(500));
+    while app_state.http_rpc_client.lock().unwrap().is_none() {
+        ticker.tick().await;
+    }
+}
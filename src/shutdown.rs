@@ -0,0 +1,55 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use tokio::time::{interval, timeout};
+
+use crate::txn_manager::send_txn;
+use crate::utils::{AppState, Executors};
+
+// Stop accepting new job intake, let already-running jobs (and their pending transactions)
+// finish, then deregister the enclave from the common chain so it isn't slashed for going
+// missing. The whole sequence - including the deregistration submission's own retries - shares a
+// single `drain_timeout` deadline, so a stuck job or a slow-to-confirm submission can't push
+// shutdown past it.
+pub async fn drain(app_state: Data<AppState>, drain_timeout: Duration) {
+    println!("Received shutdown signal, draining running jobs before deregistering...");
+    app_state.draining.store(true, Ordering::Relaxed);
+
+    let drain_sequence = async {
+        let mut ticker = interval(Duration::from_millis(500));
+        while !app_state.job_requests_running.lock().unwrap().is_empty() {
+            ticker.tick().await;
+        }
+
+        let Some(http_rpc_client) = app_state.http_rpc_client.lock().unwrap().clone() else {
+            eprintln!("No http rpc client configured, skipping on-chain deregistration");
+            return false;
+        };
+
+        let txn = Executors::new(app_state.executors_contract_addr, http_rpc_client)
+            .deregister_executor(app_state.enclave_address);
+
+        if let Err(err) = send_txn(txn).await {
+            eprintln!(
+                "Failed to submit the executor deregistration transaction: {:?}",
+                err
+            );
+            return false;
+        }
+
+        let mut ticker = interval(Duration::from_millis(500));
+        while app_state.enclave_registered.load(Ordering::Relaxed) {
+            ticker.tick().await;
+        }
+        true
+    };
+
+    match timeout(drain_timeout, drain_sequence).await {
+        Ok(true) => println!("Drain complete, the enclave is deregistered"),
+        Ok(false) => {}
+        Err(_) => eprintln!(
+            "Drain timeout elapsed before jobs finished and deregistration was confirmed, forcing shutdown"
+        ),
+    }
+}
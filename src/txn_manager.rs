@@ -0,0 +1,206 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use ethers::abi::Detokenize;
+use ethers::contract::ContractCall;
+use ethers::middleware::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, TransactionReceipt, U256};
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_TRACKED_JOBS: usize = 1024;
+
+// Outcome of a job-keyed submission, broadcast to every caller coalesced onto it. Carries the
+// receipt/error by value (rather than the original 'anyhow::Error', which isn't 'Clone') since
+// more than one waiter can receive it.
+#[derive(Clone)]
+enum TxOutcome {
+    Success(TransactionReceipt),
+    Failure(String),
+}
+
+// Bounded, in-process record of jobs with a submission already in flight, so a second
+// submission for the same job that starts while the first is still retrying doesn't race a
+// duplicate onto the chain; callers that land while one is in flight wait for its result
+// instead of submitting a second transaction. One that starts after the first has finished
+// is submitted again.
+struct PendingTxTracker {
+    order: VecDeque<U256>,
+    in_flight: HashMap<U256, broadcast::Sender<TxOutcome>>,
+}
+
+impl PendingTxTracker {
+    fn new() -> Self {
+        Self {
+            order: VecDeque::new(),
+            in_flight: HashMap::new(),
+        }
+    }
+
+    // Returns `Ok(())` if the caller should submit the transaction itself, or `Err(receiver)`
+    // with a channel that will carry the in-flight submission's outcome if one is already running
+    // for this job.
+    fn try_start(&mut self, job_id: U256) -> Result<(), broadcast::Receiver<TxOutcome>> {
+        if let Some(sender) = self.in_flight.get(&job_id) {
+            return Err(sender.subscribe());
+        }
+
+        if self.order.len() >= MAX_TRACKED_JOBS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.in_flight.remove(&oldest);
+            }
+        }
+
+        let (sender, _) = broadcast::channel(1);
+        self.order.push_back(job_id);
+        self.in_flight.insert(job_id, sender);
+        Ok(())
+    }
+
+    fn finish(&mut self, job_id: U256, outcome: TxOutcome) {
+        if let Some(sender) = self.in_flight.remove(&job_id) {
+            // No receivers is fine - it just means nothing raced this submission
+            let _ = sender.send(outcome);
+        }
+        self.order.retain(|id| id != &job_id);
+    }
+}
+
+static PENDING: Lazy<Mutex<PendingTxTracker>> = Lazy::new(|| Mutex::new(PendingTxTracker::new()));
+
+// Submit a transaction that isn't tied to a specific job (e.g. the executor deregistration call),
+// so it isn't subject to the per-job dedup below.
+pub async fn send_txn<M, D>(txn: ContractCall<M, D>) -> Result<TransactionReceipt>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    submit_with_retries(txn).await
+}
+
+// Submit `txn` for `job_id`. If another submission for the same job is already in flight - e.g.
+// from a different 'send_execution_output' task, since each task submits the responses it
+// receives one at a time - this call doesn't submit a duplicate; it waits for the in-flight
+// submission's result and returns that instead.
+pub async fn send_job_txn<M, D>(job_id: U256, txn: ContractCall<M, D>) -> Result<TransactionReceipt>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let mut waiter = match PENDING.lock().unwrap().try_start(job_id) {
+        Ok(()) => None,
+        Err(receiver) => Some(receiver),
+    };
+
+    if let Some(receiver) = &mut waiter {
+        return match receiver.recv().await {
+            Ok(TxOutcome::Success(receipt)) => Ok(receipt),
+            Ok(TxOutcome::Failure(err)) => Err(anyhow!(err)),
+            Err(_) => Err(anyhow!(
+                "The in-flight submission for job {} ended without a result",
+                job_id
+            )),
+        };
+    }
+
+    let result = submit_with_retries(txn).await;
+    let outcome = match &result {
+        Ok(receipt) => TxOutcome::Success(receipt.clone()),
+        Err(err) => TxOutcome::Failure(err.to_string()),
+    };
+    PENDING.lock().unwrap().finish(job_id, outcome);
+    result
+}
+
+// Retry `txn` with exponential backoff on transient provider errors, bumping the gas price on a
+// "replacement underpriced"/timeout error and re-reading the account nonce on "nonce too low",
+// giving up only once `MAX_ATTEMPTS` submissions have failed.
+async fn submit_with_retries<M, D>(mut txn: ContractCall<M, D>) -> Result<TransactionReceipt>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut last_err = None;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        match txn.send().await {
+            Ok(pending_txn) => match pending_txn.await {
+                Ok(Some(receipt)) => return Ok(receipt),
+                Ok(None) => {
+                    eprintln!(
+                        "Transaction dropped from the mempool, retrying (attempt {}/{})",
+                        attempt, MAX_ATTEMPTS
+                    );
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Failed while waiting for confirmation, retrying (attempt {}/{}): {:?}",
+                        attempt, MAX_ATTEMPTS, err
+                    );
+                    last_err = Some(err.to_string());
+                }
+            },
+            Err(err) => {
+                let err_string = err.to_string();
+
+                if err_string.contains("nonce too low") {
+                    let sender = *txn.tx.from().unwrap_or(&Address::zero());
+                    match txn.client.get_transaction_count(sender, None).await {
+                        Ok(nonce) => txn = txn.nonce(nonce),
+                        Err(nonce_err) => eprintln!(
+                            "Failed to re-read the account nonce after a 'nonce too low' error: {:?}",
+                            nonce_err
+                        ),
+                    }
+                } else if err_string.contains("replacement transaction underpriced")
+                    || err_string.contains("timeout")
+                {
+                    // 'gas_price()' only covers legacy/Eip2930 transactions; an Eip1559 call
+                    // prices itself through 'max_fee_per_gas'/'max_priority_fee_per_gas'
+                    // instead, so both need bumping or the retry resubmits the exact same fee
+                    match &mut txn.tx {
+                        TypedTransaction::Eip1559(inner) => {
+                            if let Some(max_fee) = inner.max_fee_per_gas {
+                                inner.max_fee_per_gas = Some(max_fee * 12 / 10);
+                            }
+                            if let Some(priority_fee) = inner.max_priority_fee_per_gas {
+                                inner.max_priority_fee_per_gas = Some(priority_fee * 12 / 10);
+                            }
+                        }
+                        _ => {
+                            if let Some(gas_price) = txn.tx.gas_price() {
+                                txn = txn.gas_price(gas_price * 12 / 10);
+                            }
+                        }
+                    }
+                } else {
+                    eprintln!(
+                        "Transient error submitting transaction, retrying (attempt {}/{}): {:?}",
+                        attempt, MAX_ATTEMPTS, err
+                    );
+                }
+
+                last_err = Some(err_string);
+            }
+        }
+
+        if attempt == MAX_ATTEMPTS {
+            break;
+        }
+        sleep(backoff).await;
+        backoff *= 2;
+    }
+
+    Err(anyhow!(
+        "Exhausted {} attempts submitting the transaction: {}",
+        MAX_ATTEMPTS,
+        last_err.unwrap_or_default()
+    ))
+}